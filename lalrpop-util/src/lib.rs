@@ -1,9 +1,22 @@
 use std::error::Error;
 use std::fmt;
 
+// Note: tracking the span of the enclosing construct for `UnrecognizedEOF`
+// (e.g. pointing at the `(` that was never closed) would require the LR
+// automaton driver backing this module to record the token that opened the
+// currently-active production. That driver isn't part of this tree, so
+// `ParseError` cannot carry that context here.
 pub mod state_machine;
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "L: serde::Serialize, T: serde::Serialize, E: serde::Serialize",
+        deserialize = "L: serde::Deserialize<'de>, T: serde::Deserialize<'de>, E: serde::Deserialize<'de>"
+    ))
+)]
 pub enum ParseError<L, T, E> {
     /// Generated by the parser when it encounters a token (or EOF) it did not
     /// expect.
@@ -92,20 +105,167 @@ impl<L, T, E> ParseError<L, T, E> {
     {
         self.map_intern(|x| x, |x| x, op)
     }
+
+    /// Rewrites the `expected` list (if any) through `op`, so grammar-internal
+    /// names can be replaced with labels suitable for showing to a user.
+    pub fn map_expected<F>(self, op: F) -> Self
+    where
+        F: Fn(String) -> String,
+    {
+        match self {
+            ParseError::UnrecognizedEOF { location, expected } => ParseError::UnrecognizedEOF {
+                location,
+                expected: expected.into_iter().map(&op).collect(),
+            },
+            ParseError::UnrecognizedToken { token, expected } => ParseError::UnrecognizedToken {
+                token,
+                expected: expected.into_iter().map(&op).collect(),
+            },
+            other => other,
+        }
+    }
+
+    /// Returns the start/end locations that best describe where this error
+    /// occurred, suitable for highlighting a span in the source text.
+    ///
+    /// `InvalidToken` and `UnrecognizedEOF` report a zero-width span at
+    /// `location`; `UnrecognizedToken` and `ExtraToken` report the span of
+    /// the offending token; `User` errors carry no location of their own,
+    /// so this returns `None`.
+    pub fn primary_span(&self) -> Option<(&L, &L)> {
+        match *self {
+            ParseError::InvalidToken { ref location } => Some((location, location)),
+            ParseError::UnrecognizedEOF { ref location, .. } => Some((location, location)),
+            ParseError::UnrecognizedToken { token: (ref start, _, ref end), .. } => {
+                Some((start, end))
+            }
+            ParseError::ExtraToken { token: (ref start, _, ref end) } => Some((start, end)),
+            ParseError::User { .. } => None,
+        }
+    }
+
+    /// Returns the set of expected tokens for this error, or an empty slice
+    /// if this variant does not carry one.
+    pub fn expected(&self) -> &[String] {
+        match *self {
+            ParseError::UnrecognizedEOF { ref expected, .. } => expected,
+            ParseError::UnrecognizedToken { ref expected, .. } => expected,
+            ParseError::InvalidToken { .. }
+            | ParseError::ExtraToken { .. }
+            | ParseError::User { .. } => &[],
+        }
+    }
+}
+
+/// A single labeled source span within a [`Diagnostic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label<L> {
+    pub start: L,
+    pub end: L,
+    pub message: String,
+}
+
+/// A library-agnostic representation of a [`ParseError`], decoupled from
+/// `fmt::Display`.
+///
+/// This is meant to be consumed by diagnostic-rendering crates such as
+/// `codespan-reporting`, `ariadne`, or `miette`, none of which this crate
+/// depends on: callers map `Diagnostic` to whichever reporter they use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic<L> {
+    /// The top-level error message.
+    pub message: String,
+
+    /// The span that should be highlighted as the primary cause of the
+    /// error, if any (`User` errors carry no span of their own).
+    pub primary_label: Option<Label<L>>,
+
+    /// Secondary notes to display alongside the primary label, such as the
+    /// set of expected tokens.
+    pub notes: Vec<String>,
+}
+
+impl<L, T, E> ParseError<L, T, E>
+where
+    L: Clone,
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    /// Converts this error into a [`Diagnostic`] that any reporter crate can
+    /// render, without reimplementing the variant-to-message mapping.
+    pub fn to_diagnostic(&self) -> Diagnostic<L> {
+        let notes = expected_note(self.expected()).into_iter().collect();
+        match *self {
+            ParseError::InvalidToken { ref location } => Diagnostic {
+                message: "invalid token".to_string(),
+                primary_label: Some(Label {
+                    start: location.clone(),
+                    end: location.clone(),
+                    message: "invalid token".to_string(),
+                }),
+                notes,
+            },
+            ParseError::UnrecognizedEOF { ref location, .. } => Diagnostic {
+                message: "unrecognized EOF".to_string(),
+                primary_label: Some(Label {
+                    start: location.clone(),
+                    end: location.clone(),
+                    message: "unexpected end of input".to_string(),
+                }),
+                notes,
+            },
+            ParseError::UnrecognizedToken { token: (ref start, ref token, ref end), .. } => {
+                Diagnostic {
+                    message: format!("unrecognized token `{}`", token),
+                    primary_label: Some(Label {
+                        start: start.clone(),
+                        end: end.clone(),
+                        message: "unexpected token".to_string(),
+                    }),
+                    notes,
+                }
+            }
+            ParseError::ExtraToken { token: (ref start, ref token, ref end) } => Diagnostic {
+                message: format!("extra token `{}`", token),
+                primary_label: Some(Label {
+                    start: start.clone(),
+                    end: end.clone(),
+                    message: "unexpected extra token".to_string(),
+                }),
+                notes,
+            },
+            ParseError::User { ref error } => Diagnostic {
+                message: error.to_string(),
+                primary_label: None,
+                notes: vec![],
+            },
+        }
+    }
+}
+
+/// Builds a single human-readable note out of an expected-token list, e.g.
+/// "expected one of t1, t2 or t3".
+fn expected_note(expected: &[String]) -> Option<String> {
+    if expected.is_empty() {
+        return None;
+    }
+    let mut note = String::from("expected one of ");
+    for (i, e) in expected.iter().enumerate() {
+        if i > 0 {
+            note.push_str(if i < expected.len() - 1 { ", " } else { " or " });
+        }
+        note.push_str(e);
+    }
+    Some(note)
 }
 
 /// Format a list of expected tokens.
 fn fmt_expected(f: &mut fmt::Formatter, expected: &[String]) -> fmt::Result {
-    if !expected.is_empty() {
+    if let Some(note) = expected_note(expected) {
         writeln!(f, "")?;
-        for (i, e) in expected.iter().enumerate() {
-            let sep = match i {
-                0 => "Expected one of",
-                _ if i < expected.len() - 1 => ",",
-                // Last expected message to be written
-                _ => " or",
-            };
-            write!(f, "{} {}", sep, e)?;
+        let mut chars = note.chars();
+        if let Some(first) = chars.next() {
+            write!(f, "{}{}", first.to_uppercase(), chars.as_str())?;
         }
     }
     Ok(())
@@ -149,6 +309,14 @@ where
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "L: serde::Serialize, T: serde::Serialize, E: serde::Serialize",
+        deserialize = "L: serde::Deserialize<'de>, T: serde::Deserialize<'de>, E: serde::Deserialize<'de>"
+    ))
+)]
 pub struct ErrorRecovery<L, T, E> {
     pub error: ParseError<L, T, E>,
     pub dropped_tokens: Vec<(L, T, L)>,
@@ -195,6 +363,26 @@ macro_rules! lalrpop_mod {
 mod tests {
     use super::*;
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let err = ParseError::UnrecognizedEOF::<i32, &str, &str> {
+            location: 11,
+            expected: vec!["t4".to_string()],
+        };
+        let json = serde_json::to_string(&err).unwrap();
+        let round_tripped: ParseError<i32, &str, &str> = serde_json::from_str(&json).unwrap();
+        assert_eq!(err, round_tripped);
+
+        let recovery = ErrorRecovery::<i32, &str, &str> {
+            error: ParseError::ExtraToken { token: (6, "t5", 7) },
+            dropped_tokens: vec![(6, "t5", 7), (7, "t6", 8)],
+        };
+        let json = serde_json::to_string(&recovery).unwrap();
+        let round_tripped: ErrorRecovery<i32, &str, &str> = serde_json::from_str(&json).unwrap();
+        assert_eq!(recovery, round_tripped);
+    }
+
     #[test]
     fn test() {
         let err = ParseError::UnrecognizedToken::<i32, &str, &str> {
@@ -210,4 +398,89 @@ mod tests {
              Expected one of t1, t2 or t3"
         );
     }
+
+    #[test]
+    fn test_primary_span_and_expected() {
+        let err = ParseError::InvalidToken::<i32, &str, &str> { location: 7 };
+        assert_eq!(err.primary_span(), Some((&7, &7)));
+        assert!(err.expected().is_empty());
+
+        let err = ParseError::UnrecognizedEOF::<i32, &str, &str> {
+            location: 9,
+            expected: vec!["t1".to_string()],
+        };
+        assert_eq!(err.primary_span(), Some((&9, &9)));
+        assert_eq!(err.expected(), &["t1".to_string()][..]);
+
+        let err = ParseError::UnrecognizedToken::<i32, &str, &str> {
+            token: (3, "t0", 5),
+            expected: vec!["t2".to_string()],
+        };
+        assert_eq!(err.primary_span(), Some((&3, &5)));
+        assert_eq!(err.expected(), &["t2".to_string()][..]);
+
+        let err = ParseError::ExtraToken::<i32, &str, &str> { token: (10, "t3", 12) };
+        assert_eq!(err.primary_span(), Some((&10, &12)));
+        assert!(err.expected().is_empty());
+
+        let err = ParseError::User::<i32, &str, &str> { error: "oops" };
+        assert_eq!(err.primary_span(), None);
+        assert!(err.expected().is_empty());
+    }
+
+    #[test]
+    fn test_to_diagnostic() {
+        let err = ParseError::InvalidToken::<i32, &str, &str> { location: 4 };
+        let diagnostic = err.to_diagnostic();
+        assert_eq!(diagnostic.message, "invalid token");
+        let label = diagnostic.primary_label.unwrap();
+        assert_eq!((label.start, label.end), (4, 4));
+        assert!(diagnostic.notes.is_empty());
+
+        let err = ParseError::UnrecognizedEOF::<i32, &str, &str> {
+            location: 6,
+            expected: vec!["t1".to_string()],
+        };
+        let diagnostic = err.to_diagnostic();
+        assert_eq!(diagnostic.message, "unrecognized EOF");
+        let label = diagnostic.primary_label.unwrap();
+        assert_eq!((label.start, label.end), (6, 6));
+        assert_eq!(diagnostic.notes, vec!["expected one of t1".to_string()]);
+
+        let err = ParseError::UnrecognizedToken::<i32, &str, &str> {
+            token: (1, "t0", 2),
+            expected: vec!["t1".to_string(), "t2".to_string()],
+        };
+        let diagnostic = err.to_diagnostic();
+        assert_eq!(diagnostic.message, "unrecognized token `t0`");
+        let label = diagnostic.primary_label.unwrap();
+        assert_eq!((label.start, label.end), (1, 2));
+        assert_eq!(diagnostic.notes, vec!["expected one of t1 or t2".to_string()]);
+
+        let err = ParseError::ExtraToken::<i32, &str, &str> { token: (8, "t3", 9) };
+        let diagnostic = err.to_diagnostic();
+        assert_eq!(diagnostic.message, "extra token `t3`");
+        let label = diagnostic.primary_label.unwrap();
+        assert_eq!((label.start, label.end), (8, 9));
+        assert!(diagnostic.notes.is_empty());
+
+        let err = ParseError::User::<i32, &str, &str> { error: "oops" };
+        let diagnostic = err.to_diagnostic();
+        assert_eq!(diagnostic.message, "oops");
+        assert!(diagnostic.primary_label.is_none());
+    }
+
+    #[test]
+    fn test_map_expected() {
+        let err = ParseError::UnrecognizedToken::<i32, &str, &str> {
+            token: (1, "t0", 2),
+            expected: vec!["RPAREN".to_string()],
+        };
+        let err = err.map_expected(|name| match name.as_str() {
+            "RPAREN" => ")".to_string(),
+            other => other.to_string(),
+        });
+        assert_eq!(err.expected(), &[")".to_string()][..]);
+    }
+
 }